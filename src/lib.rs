@@ -1,6 +1,6 @@
 use std::{
     fmt::Debug,
-    io::{Cursor, Read, Seek},
+    io::{Cursor, Read, Seek, Write},
     path::{Path, PathBuf},
 };
 
@@ -24,16 +24,31 @@ pub enum MsgError {
 
 type Result<S> = std::result::Result<S, MsgError>;
 
+/// A `(display name, address)` pair, as carried throughout the high-level API.
+type Mailbox = (String, String);
+/// A message's recipients partitioned into `(to, cc, bcc)`.
+type Recipients = (Vec<Mailbox>, Vec<Mailbox>, Vec<Mailbox>);
+
 /// A low-level API for reading data from a .msg file.
 pub struct MsgReader<'c, 'p, F> {
     inner: &'c mut CompoundFile<F>,
     path: &'p Path,
+    /// Cached message code page, derived on first need from the codepage
+    /// properties (see [`MsgReader::message_codepage`]).
+    codepage: Option<u32>,
 }
 
 #[derive(Clone)]
 pub struct Attachment {
     pub name: String,
     pub data: Vec<u8>,
+    /// The MIME type from `PR_ATTACH_MIME_TAG`, e.g. `image/png`, if present.
+    pub mime_type: Option<String>,
+    /// The `PR_ATTACH_CONTENT_ID` used to reference inline parts as `cid:…`.
+    pub content_id: Option<String>,
+    /// Whether this attachment is an inline/embedded part (e.g. an image
+    /// referenced from the HTML body) rather than a genuine file attachment.
+    pub is_inline: bool,
 }
 
 impl Debug for Attachment {
@@ -41,6 +56,9 @@ impl Debug for Attachment {
         f.debug_struct("Attachment")
             .field("name", &self.name)
             .field("data of size", &self.data.len())
+            .field("mime_type", &self.mime_type)
+            .field("content_id", &self.content_id)
+            .field("is_inline", &self.is_inline)
             .finish()
     }
 }
@@ -57,6 +75,147 @@ pub struct Email {
     pub body: Option<String>,
     pub attachments: Vec<Attachment>,
     pub embedded_messages: Vec<Email>,
+    /// The parsed `PR_TRANSPORT_MESSAGE_HEADERS`, as an ordered list of
+    /// `(name, value)` pairs. Empty when the message has no transport headers.
+    pub raw_headers: Vec<(String, String)>,
+}
+
+impl Email {
+    /// Look up a transport header by name, case-insensitively, returning the
+    /// first matching value.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        header_get(&self.raw_headers, name)
+    }
+    /// The `Message-ID` of this message, if present in the transport headers.
+    pub fn message_id(&self) -> Option<String> {
+        self.header("Message-ID").map(|s| s.trim().to_string())
+    }
+    /// The `In-Reply-To` message id, if present in the transport headers.
+    pub fn in_reply_to(&self) -> Option<String> {
+        self.header("In-Reply-To").map(|s| s.trim().to_string())
+    }
+    /// The `References` message ids, newest last, parsed from the transport
+    /// headers. Empty when the header is absent.
+    pub fn references(&self) -> Vec<String> {
+        self.header("References")
+            .map(|s| s.split_whitespace().map(|x| x.to_string()).collect())
+            .unwrap_or_default()
+    }
+    /// The `Reply-To` address as a `(display name, address)` pair.
+    pub fn reply_to(&self) -> Option<(String, String)> {
+        self.header("Reply-To").map(parse_address)
+    }
+    /// Whether the message carries at least one genuine (non-inline) file
+    /// attachment, so callers can cheaply flag messages with "true" attachments.
+    pub fn has_real_attachments(&self) -> bool {
+        self.attachments.iter().any(|a| !a.is_inline)
+    }
+
+    /// Serialize this message to a standards-compliant RFC 5322 / MIME `.eml`
+    /// byte stream — the inverse of the parsing path. See [`Email::write_eml`].
+    pub fn to_eml(&self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        self.write_eml(&mut out)?;
+        Ok(out)
+    }
+
+    /// Write this message as a `multipart/mixed` MIME document: the `From`/`To`/
+    /// `Cc`/`Subject`/`Date` headers (RFC 2047-encoding any non-ASCII text), the
+    /// body as `text/html` (or as `application/rtf` when it is decompressed
+    /// RTF), each [`Attachment`] as a base64 part, and each embedded message
+    /// recursively as a `message/rfc822` part.
+    pub fn write_eml<W: Write>(&self, w: &mut W) -> Result<()> {
+        let boundary = self.eml_boundary();
+        if let Some((name, addr)) = &self.from {
+            write_header_line(w, "From", &format_address(name, addr))?;
+        }
+        if !self.to.is_empty() {
+            write_header_line(w, "To", &format_address_list(&self.to))?;
+        }
+        if !self.cc.is_empty() {
+            write_header_line(w, "Cc", &format_address_list(&self.cc))?;
+        }
+        if let Some(subject) = &self.subject {
+            write_header_line(w, "Subject", &encode_header_word(subject))?;
+        }
+        if let Some(date) = &self.sent_date {
+            write_header_line(w, "Date", &date.to_rfc2822())?;
+        }
+        write_line(w, "MIME-Version: 1.0")?;
+        write_line(
+            w,
+            &format!("Content-Type: multipart/mixed; boundary=\"{boundary}\""),
+        )?;
+        write_line(w, "")?;
+
+        // Body part. `body()` falls back to decompressed RTF when no HTML body
+        // is present, so label it by sniffing the RTF signature rather than
+        // always claiming `text/html`.
+        let body = self.body.as_deref().unwrap_or_default();
+        let body_type = if body.trim_start().starts_with("{\\rtf") {
+            "application/rtf"
+        } else {
+            "text/html; charset=utf-8"
+        };
+        write_line(w, &format!("--{boundary}"))?;
+        write_line(w, &format!("Content-Type: {body_type}"))?;
+        write_line(w, "Content-Transfer-Encoding: base64")?;
+        write_line(w, "")?;
+        write_line(w, &base64_wrap(body.as_bytes()))?;
+
+        // Attachment parts.
+        for att in &self.attachments {
+            write_line(w, &format!("--{boundary}"))?;
+            let mime = att.mime_type.as_deref().unwrap_or("application/octet-stream");
+            write_line(w, &format!("Content-Type: {mime}"))?;
+            write_line(w, "Content-Transfer-Encoding: base64")?;
+            let disposition = if att.is_inline { "inline" } else { "attachment" };
+            write_header_line(
+                w,
+                "Content-Disposition",
+                &format!("{disposition}; filename={}", format_filename(&att.name)),
+            )?;
+            if let Some(cid) = &att.content_id {
+                let cid = if cid.starts_with('<') {
+                    cid.clone()
+                } else {
+                    format!("<{cid}>")
+                };
+                write_header_line(w, "Content-ID", &cid)?;
+            }
+            write_line(w, "")?;
+            write_line(w, &base64_wrap(&att.data))?;
+        }
+
+        // Embedded messages as message/rfc822 parts.
+        for emb in &self.embedded_messages {
+            write_line(w, &format!("--{boundary}"))?;
+            write_line(w, "Content-Type: message/rfc822")?;
+            write_line(w, "Content-Disposition: attachment")?;
+            write_line(w, "")?;
+            emb.write_eml(w)?;
+        }
+
+        write_line(w, &format!("--{boundary}--"))?;
+        Ok(())
+    }
+
+    /// A boundary marker derived deterministically from the message contents, so
+    /// serializing the same `Email` twice yields an identical document.
+    fn eml_boundary(&self) -> String {
+        use std::hash::{Hash, Hasher};
+        let mut h = std::collections::hash_map::DefaultHasher::new();
+        self.subject.hash(&mut h);
+        self.attachments.len().hash(&mut h);
+        if let Some((name, addr)) = &self.from {
+            name.hash(&mut h);
+            addr.hash(&mut h);
+        }
+        for (_, addr) in &self.to {
+            addr.hash(&mut h);
+        }
+        format!("----=_tiny_msg_{:016x}", h.finish())
+    }
 }
 
 impl Email {
@@ -70,11 +229,19 @@ impl Email {
     fn from_path_internal(file: &Path, subpath: &Path) -> Self {
         let mut comp = cfb::open(file).unwrap();
         let mut reader = MsgReader::new(&mut comp, subpath);
-        let from = reader.from().ok();
+        let raw_headers = reader.raw_headers().unwrap_or_default();
+        let from = reader
+            .from()
+            .ok()
+            .or_else(|| header_get(&raw_headers, "From").map(parse_address));
         let sent_date = reader.sent_date().ok();
-        let to = reader.to().unwrap_or_default();
-        let cc = reader.cc().unwrap_or_default();
-        let bcc = reader.bcc().unwrap_or_default();
+        let (mut to, mut cc, bcc) = reader.recipients_partitioned().unwrap_or_default();
+        if to.is_empty() {
+            to = parse_address_list(header_get(&raw_headers, "To"));
+        }
+        if cc.is_empty() {
+            cc = parse_address_list(header_get(&raw_headers, "Cc"));
+        }
         let subject = reader.pr_subject().ok();
         let body = reader.body().ok();
         let attachments = reader.attachments().unwrap_or_default();
@@ -93,17 +260,26 @@ impl Email {
             body,
             attachments,
             embedded_messages,
+            raw_headers,
         }
     }
     fn from_bytes_internal(bytes: &[u8], subpath: &Path) -> Self {
         let cur = Cursor::new(bytes);
         let mut comp = CompoundFile::open(cur).unwrap();
         let mut reader = MsgReader::new(&mut comp, subpath);
-        let from = reader.from().ok();
+        let raw_headers = reader.raw_headers().unwrap_or_default();
+        let from = reader
+            .from()
+            .ok()
+            .or_else(|| header_get(&raw_headers, "From").map(parse_address));
         let sent_date = reader.sent_date().ok();
-        let to = reader.to().unwrap_or_default();
-        let cc = reader.cc().unwrap_or_default();
-        let bcc = reader.bcc().unwrap_or_default();
+        let (mut to, mut cc, bcc) = reader.recipients_partitioned().unwrap_or_default();
+        if to.is_empty() {
+            to = parse_address_list(header_get(&raw_headers, "To"));
+        }
+        if cc.is_empty() {
+            cc = parse_address_list(header_get(&raw_headers, "Cc"));
+        }
         let subject = reader.pr_subject().ok();
         let body = reader.body().ok();
         let attachments = reader.attachments().unwrap_or_default();
@@ -122,6 +298,7 @@ impl Email {
             body,
             attachments,
             embedded_messages,
+            raw_headers,
         }
     }
 }
@@ -131,18 +308,63 @@ where
     F: Read + Seek,
 {
     pub fn new(inner: &'c mut CompoundFile<F>, path: &'p Path) -> Self {
-        Self { inner, path }
+        Self {
+            inner,
+            path,
+            codepage: None,
+        }
     }
 
     fn read_simple_string(&mut self, prop: &str) -> Result<String> {
-        let mut content = self
-            .inner
-            .open_stream(self.path.join(format!("__substg1.0_{prop}001F")))?;
+        let base = self.path.join(format!("__substg1.0_{prop}"));
+        self.read_string_stream(&base)
+    }
+    /// Read a PtypString stream, preferring the `001F` (UTF-16LE) variant and
+    /// falling back to the `001E` 8-bit variant decoded with the message code
+    /// page. `base` is the stream path without its 4-hex property-type suffix.
+    fn read_string_stream(&mut self, base: &Path) -> Result<String> {
+        let unicode = with_type_suffix(base, "001F");
+        if let Ok(mut content) = self.inner.open_stream(&unicode) {
+            let mut buf = vec![];
+            content.read_to_end(&mut buf)?;
+            return String::from_utf16(&pack_u8s_to_u16s_le_padded(&buf))
+                .map_err(|_e| MsgError::Encoding)
+                .map(|x| x.trim_end_matches('\0').to_string());
+        }
+        let ansi = with_type_suffix(base, "001E");
+        let mut content = self.inner.open_stream(&ansi)?;
         let mut buf = vec![];
-        content.read_to_end(&mut buf).unwrap();
-        String::from_utf16(&pack_u8s_to_u16s_le_padded(&buf))
-            .map_err(|_e| MsgError::Encoding)
-            .map(|x| x.trim_end_matches('\0').to_string())
+        content.read_to_end(&mut buf)?;
+        let charset = codepage_to_charset(self.message_codepage());
+        Ok(decode_charset(&buf, charset)
+            .trim_end_matches('\0')
+            .to_string())
+    }
+    /// The message code page, read once from `PR_MESSAGE_CODEPAGE` (tag
+    /// `3FFD0003`) or `PR_INTERNET_CODEPAGE` (tag `3FDE0003`) and cached,
+    /// defaulting to Windows-1252 (1252) when neither property is present.
+    fn message_codepage(&mut self) -> u32 {
+        if let Some(cp) = self.codepage {
+            return cp;
+        }
+        let storage = self.path.to_path_buf();
+        let header_len = self.message_header_len();
+        let cp = self
+            .read_property_int(&storage, 0x3FFD_0003, header_len)
+            .or_else(|| self.read_property_int(&storage, 0x3FDE_0003, header_len))
+            .unwrap_or(1252);
+        self.codepage = Some(cp);
+        cp
+    }
+    /// The length of the fixed header preceding the property records in this
+    /// storage's `__properties_version1.0` stream: 32 bytes for the top-level
+    /// message, 24 for an embedded message (MS-OXMSG §2.4.2.1–2.4.2.2).
+    fn message_header_len(&self) -> usize {
+        if self.path == Path::new("/") {
+            32
+        } else {
+            24
+        }
     }
     fn read_simple_binary(&mut self, prop: &str) -> Result<Vec<u8>> {
         let mut content = self
@@ -159,18 +381,17 @@ where
         Ok(buf)
     }
     pub fn read_path_as_string(&mut self, subpath: &Path) -> Result<String> {
-        let mut content = self.inner.open_stream(self.path.join(subpath))?;
-        let mut buf = vec![];
-        content.read_to_end(&mut buf).unwrap();
-        String::from_utf16(&pack_u8s_to_u16s_le_padded(&buf))
-            .map_err(|_e| MsgError::Encoding)
-            .map(|x| x.trim_end_matches('\0').to_string())
+        // Callers pass a path ending in the `001F` property-type suffix; strip
+        // it so the UTF-16/ANSI fallback in `read_string_stream` can apply.
+        let base = strip_type_suffix(&self.path.join(subpath));
+        self.read_string_stream(&base)
     }
     pub fn pr_subject(&mut self) -> Result<String> {
         self.read_simple_string("0037") // PR_SUBJECT
+            .map(|s| decode_encoded_words(&s))
     }
     pub fn pr_sender_name(&mut self) -> Result<String> {
-        self.read_simple_string("0C1A")
+        self.read_simple_string("0C1A").map(|s| decode_encoded_words(&s))
     }
     pub fn pr_sender_email_adress_str(&mut self) -> Result<String> {
         self.read_simple_string("0C19")
@@ -192,6 +413,11 @@ where
     pub fn pr_transport_message_headers(&mut self) -> Result<String> {
         self.read_simple_string("007D")
     }
+    /// Parse `PR_TRANSPORT_MESSAGE_HEADERS` into an ordered, case-insensitive
+    /// multimap of `(name, value)` pairs, folding RFC 5322 continuation lines.
+    pub fn raw_headers(&mut self) -> Result<Vec<(String, String)>> {
+        Ok(parse_raw_headers(&self.pr_transport_message_headers()?))
+    }
     pub fn pr_body_html(&mut self) -> Result<String> {
         let bin = self.read_simple_binary("1013")?;
         String::from_utf8(bin).map_err(|_| MsgError::Encoding)
@@ -207,19 +433,16 @@ where
         self.pr_body_html().or_else(|_| self.rtf())
     }
     pub fn sent_date(&mut self) -> Result<DateTime<Utc>> {
-        let headers = self.pr_transport_message_headers()?;
-        let dateline = headers
-            .lines()
-            .find(|x| x.starts_with("Date"))
-            .ok_or(MsgError::Encoding)?
-            .split_once(": ")
-            .ok_or(MsgError::Encoding)?
-            .1;
+        let headers = self.raw_headers()?;
+        let dateline = header_get(&headers, "Date").ok_or(MsgError::Encoding)?;
         chrono::DateTime::parse_from_rfc2822(dateline)
             .map_err(|_| MsgError::Encoding)
             .map(|d| d.with_timezone(&Utc))
     }
-    fn recipients(&mut self) -> Result<Vec<(String, String)>> {
+    /// Read every `__recip_version1.0_*` storage once, returning each
+    /// recipient's `PR_RECIPIENT_TYPE` (`None` when the property stream is
+    /// absent) alongside its `(display name, address)` pair.
+    fn recipients_by_type(&mut self) -> Result<Vec<(Option<i32>, Mailbox)>> {
         let recip_paths: Vec<_> = self
             .inner
             .read_storage(self.path)?
@@ -231,39 +454,91 @@ where
             .map(|r| {
                 let name = self.read_path_as_string(&r.join("__substg1.0_3001001F"))?;
                 let address = self.read_path_as_string(&r.join("__substg1.0_39FE001F"))?;
-                Ok((name, address))
+                let rtype = self.read_recipient_type(r);
+                Ok((rtype, (decode_encoded_words(&name), address)))
             })
             .collect()
     }
+    /// Read `PR_RECIPIENT_TYPE` (tag `0C150003`) for one recipient storage as a
+    /// little-endian `i32` (1=To, 2=Cc, 3=Bcc). The property is fixed-width and
+    /// lives inline in the storage's `__properties_version1.0` stream, not as a
+    /// `__substg1.0_*` substream (MS-OXMSG §2.4.2.3).
+    fn read_recipient_type(&mut self, recip: &Path) -> Option<i32> {
+        self.read_property_int(recip, 0x0C15_0003, 8)
+            .map(|v| v as i32)
+    }
+    /// Read a fixed-width PtypInteger32 property inline from a storage's
+    /// `__properties_version1.0` stream. MAPI packs these 8-byte values into
+    /// 16-byte records following a fixed header (8 bytes for attachment and
+    /// recipient objects, 24 for an embedded message, 32 for the top level —
+    /// MS-OXMSG §2.4.2), rather than giving each its own substream. Each record
+    /// begins with the property tag as a little-endian `u32`; returns the value
+    /// word of the first matching record, or `None` if the tag is absent.
+    fn read_property_int(&mut self, storage: &Path, tag: u32, header_len: usize) -> Option<u32> {
+        let mut content = self
+            .inner
+            .open_stream(storage.join("__properties_version1.0"))
+            .ok()?;
+        let mut buf = vec![];
+        content.read_to_end(&mut buf).ok()?;
+        let wanted = tag.to_le_bytes();
+        let mut off = header_len;
+        while off + 16 <= buf.len() {
+            if buf[off..off + 4] == wanted {
+                let value: [u8; 4] = buf[off + 8..off + 12].try_into().ok()?;
+                return Some(u32::from_le_bytes(value));
+            }
+            off += 16;
+        }
+        None
+    }
+    /// Classify every recipient into `(to, cc, bcc)` in a single pass over the
+    /// recipient storages, using `PR_RECIPIENT_TYPE`. Recipients whose type is
+    /// absent or not 1, 2 or 3 are omitted. When no recipient carries that
+    /// property, fall back to matching display names against the
+    /// `PR_DISPLAY_TO/CC/BCC` header strings.
+    pub fn recipients_partitioned(&mut self) -> Result<Recipients> {
+        let recips = self.recipients_by_type()?;
+        if recips.iter().any(|(t, _)| t.is_some()) {
+            let (mut to, mut cc, mut bcc) = (vec![], vec![], vec![]);
+            for (rtype, addr) in recips {
+                match rtype {
+                    Some(1) => to.push(addr),
+                    Some(2) => cc.push(addr),
+                    Some(3) => bcc.push(addr),
+                    _ => {}
+                }
+            }
+            return Ok((to, cc, bcc));
+        }
+        // Fallback: the pre-PR_RECIPIENT_TYPE behavior of matching each display
+        // name exactly against the `;`-separated entries of the PR_DISPLAY_*
+        // strings.
+        let all: Vec<(String, String)> = recips.into_iter().map(|(_, a)| a).collect();
+        let to_field = self.read_simple_string("0E04").unwrap_or_default();
+        let cc_field = self.read_simple_string("0E03").unwrap_or_default();
+        let bcc_field = self.read_simple_string("0E02").unwrap_or_default();
+        let filter_by = |field: &str| -> Vec<(String, String)> {
+            let list: Vec<&str> = field.split(';').map(|n| n.trim()).collect();
+            all.iter()
+                .filter(|(k, _v)| list.contains(&k.as_str()))
+                .cloned()
+                .collect()
+        };
+        Ok((
+            filter_by(&to_field),
+            filter_by(&cc_field),
+            filter_by(&bcc_field),
+        ))
+    }
     pub fn to(&mut self) -> Result<Vec<(String, String)>> {
-        let to_field = self.read_simple_string("0E04")?;
-        let to_list: Vec<_> = to_field.split(";").map(|n| n.trim()).collect();
-        let output: Vec<(String, String)> = self
-            .recipients()?
-            .into_iter()
-            .filter(|(k, _v)| to_list.contains(&&k[..]))
-            .collect();
-        Ok(output)
+        Ok(self.recipients_partitioned()?.0)
     }
     pub fn cc(&mut self) -> Result<Vec<(String, String)>> {
-        let cc_field = self.read_simple_string("0E03")?;
-        let cc_list: Vec<_> = cc_field.split(";").map(|n| n.trim()).collect();
-        let output: Vec<(String, String)> = self
-            .recipients()?
-            .into_iter()
-            .filter(|(k, _v)| cc_list.contains(&&k[..]))
-            .collect();
-        Ok(output)
+        Ok(self.recipients_partitioned()?.1)
     }
     pub fn bcc(&mut self) -> Result<Vec<(String, String)>> {
-        let bcc_field = self.read_simple_string("0E02")?;
-        let bcc_list: Vec<_> = bcc_field.split(";").map(|n| n.trim()).collect();
-        let output: Vec<(String, String)> = self
-            .recipients()?
-            .into_iter()
-            .filter(|(k, _v)| bcc_list.contains(&&k[..]))
-            .collect();
-        Ok(output)
+        Ok(self.recipients_partitioned()?.2)
     }
     pub fn attachments(&mut self) -> Result<Vec<Attachment>> {
         let attachment_paths: Vec<_> = self
@@ -272,6 +547,9 @@ where
             .filter(|x| x.name().starts_with("__attach_version1.0_"))
             .map(|r| r.path().to_owned())
             .collect();
+        // Fetch the HTML body once so we can detect attachments referenced as
+        // `cid:…` inline images.
+        let body = self.body().ok();
         let res = attachment_paths
             .iter()
             .flat_map(|a| {
@@ -279,7 +557,35 @@ where
                     .read_path_as_string(&a.join("__substg1.0_3704001F"))
                     .or_else(|_| self.read_path_as_string(&a.join("__substg1.0_3001001F")))?;
                 let data = self.read_path_as_binary(&a.join("__substg1.0_37010102"))?;
-                let output: Result<Attachment> = Ok(Attachment { name, data });
+                let mime_type = self
+                    .read_path_as_string(&a.join("__substg1.0_370E001F"))
+                    .ok();
+                let content_id = self
+                    .read_path_as_string(&a.join("__substg1.0_3712001F"))
+                    .ok();
+                // PR_ATTACH_FLAGS (0x37140003) and PR_RENDERING_POSITION
+                // (0x370B0003) are fixed-width integers stored inline in the
+                // attachment storage's `__properties_version1.0` stream.
+                let flags = self.read_property_int(a, 0x3714_0003, 8);
+                let rendering = self.read_property_int(a, 0x370B_0003, 8);
+                // Inline if the HTML body references the content-id, or the
+                // ATT_MHTML_REF flag (0x4) is set, or a rendering position is
+                // assigned (anything but the sentinel -1).
+                let referenced = content_id.as_deref().is_some_and(|cid| {
+                    let bare = cid.trim_start_matches('<').trim_end_matches('>');
+                    body.as_deref()
+                        .is_some_and(|b| b.contains(&format!("cid:{bare}")))
+                });
+                let is_inline = referenced
+                    || flags.is_some_and(|f| f & 0x4 != 0)
+                    || rendering.is_some_and(|r| r != 0xFFFF_FFFF);
+                let output: Result<Attachment> = Ok(Attachment {
+                    name,
+                    data,
+                    mime_type,
+                    content_id,
+                    is_inline,
+                });
                 output
             })
             .collect();
@@ -301,6 +607,421 @@ where
     }
 }
 
+/// Write a single CRLF-terminated line.
+fn write_line<W: Write>(w: &mut W, line: &str) -> Result<()> {
+    w.write_all(line.as_bytes())?;
+    w.write_all(b"\r\n")?;
+    Ok(())
+}
+
+/// Write a `Name: value` header line, stripping any control characters from the
+/// value first so a stray CR/LF (e.g. from a crafted subject or content-id)
+/// cannot inject additional headers.
+fn write_header_line<W: Write>(w: &mut W, name: &str, value: &str) -> Result<()> {
+    write_line(w, &format!("{name}: {}", sanitize_header_value(value)))
+}
+
+/// Replace control characters (including CR and LF) with spaces so a header
+/// value stays on a single logical line.
+fn sanitize_header_value(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect()
+}
+
+/// Render an attachment file name as a `Content-Disposition`/`Content-Type`
+/// `filename` parameter value: a quoted-string with `"`/`\` escaped for ASCII
+/// names, or an RFC 2047 encoded-word for names carrying non-ASCII characters.
+/// Control characters are dropped either way.
+fn format_filename(name: &str) -> String {
+    let cleaned: String = name.chars().filter(|c| !c.is_control()).collect();
+    if cleaned.is_ascii() {
+        let escaped = cleaned.replace('\\', "\\\\").replace('"', "\\\"");
+        format!("\"{escaped}\"")
+    } else {
+        encode_header_word(&cleaned)
+    }
+}
+
+/// Format a `(display name, address)` pair as an RFC 5322 mailbox, RFC
+/// 2047-encoding the display name when it is not plain ASCII.
+fn format_address(name: &str, addr: &str) -> String {
+    if name.is_empty() {
+        addr.to_string()
+    } else {
+        format!("{} <{}>", encode_header_word(name), addr)
+    }
+}
+
+/// Format a list of address pairs as a comma-separated header value.
+fn format_address_list(addrs: &[(String, String)]) -> String {
+    addrs
+        .iter()
+        .map(|(name, addr)| format_address(name, addr))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// RFC 2047-encode `s` as a UTF-8 base64 encoded-word when it contains
+/// non-ASCII characters; otherwise return it unchanged.
+fn encode_header_word(s: &str) -> String {
+    if s.is_ascii() {
+        s.to_string()
+    } else {
+        format!("=?utf-8?B?{}?=", base64_encode(s.as_bytes()))
+    }
+}
+
+/// Standard-alphabet base64 encode.
+fn base64_encode(data: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b0 = chunk[0];
+        let b1 = chunk.get(1).copied().unwrap_or(0);
+        let b2 = chunk.get(2).copied().unwrap_or(0);
+        let n = ((b0 as u32) << 16) | ((b1 as u32) << 8) | (b2 as u32);
+        out.push(TABLE[(n >> 18 & 0x3F) as usize] as char);
+        out.push(TABLE[(n >> 12 & 0x3F) as usize] as char);
+        out.push(if chunk.len() > 1 {
+            TABLE[(n >> 6 & 0x3F) as usize] as char
+        } else {
+            '='
+        });
+        out.push(if chunk.len() > 2 {
+            TABLE[(n & 0x3F) as usize] as char
+        } else {
+            '='
+        });
+    }
+    out
+}
+
+/// Base64 encode `data` and wrap it to 76-column lines joined by CRLF.
+fn base64_wrap(data: &[u8]) -> String {
+    let encoded = base64_encode(data);
+    encoded
+        .as_bytes()
+        .chunks(76)
+        .map(|c| std::str::from_utf8(c).unwrap())
+        .collect::<Vec<_>>()
+        .join("\r\n")
+}
+
+/// Append a 4-hex property-type suffix (e.g. `001F`) to a substream path whose
+/// file name carries no type suffix yet.
+fn with_type_suffix(base: &Path, suffix: &str) -> PathBuf {
+    let mut p = base.to_path_buf();
+    let name = format!(
+        "{}{}",
+        base.file_name().unwrap_or_default().to_string_lossy(),
+        suffix
+    );
+    p.set_file_name(name);
+    p
+}
+
+/// Remove the trailing 4-character property-type suffix from a substream path,
+/// yielding the bare base used by [`MsgReader::read_string_stream`].
+fn strip_type_suffix(path: &Path) -> PathBuf {
+    let mut p = path.to_path_buf();
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let base = name.get(..name.len().saturating_sub(4)).unwrap_or(&name);
+    p.set_file_name(base);
+    p
+}
+
+/// Map a numeric Windows code page to a charset label understood by
+/// [`decode_charset`]. The single-byte windows-125x pages are transcoded
+/// directly; DBCS pages (e.g. 932 Shift-JIS, 936 GBK) are not yet decoded and
+/// fall back to Windows-1252.
+fn codepage_to_charset(codepage: u32) -> &'static str {
+    match codepage {
+        65001 => "utf-8",
+        28591 => "iso-8859-1",
+        1250 => "windows-1250",
+        1251 => "windows-1251",
+        1253 => "windows-1253",
+        1254 => "windows-1254",
+        1255 => "windows-1255",
+        1256 => "windows-1256",
+        1257 => "windows-1257",
+        1258 => "windows-1258",
+        _ => "windows-1252",
+    }
+}
+
+/// Parse raw RFC 5322 transport headers into an ordered list of
+/// `(name, value)` pairs. A line beginning with a space or tab is a folded
+/// continuation of the preceding header's value and is appended to it.
+fn parse_raw_headers(raw: &str) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for raw_line in raw.split('\n') {
+        let line = raw_line.strip_suffix('\r').unwrap_or(raw_line);
+        if line.is_empty() {
+            // A blank line terminates the header block.
+            break;
+        }
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(last) = headers.last_mut() {
+                last.1.push(' ');
+                last.1.push_str(line.trim_start());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim_start().to_string()));
+        }
+    }
+    headers
+}
+
+/// Look up a header value by name, case-insensitively, returning the first match.
+fn header_get<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// Split a single `(display name, address)` pair out of an address header
+/// fragment such as `"Jane Doe" <jane@example.com>` or a bare `addr@host`.
+fn parse_address(raw: &str) -> (String, String) {
+    let s = raw.trim();
+    if let Some(start) = s.rfind('<') {
+        if let Some(rel_end) = s[start..].find('>') {
+            let address = s[start + 1..start + rel_end].trim().to_string();
+            let name = s[..start].trim().trim_matches('"').trim().to_string();
+            return (name, address);
+        }
+    }
+    (String::new(), s.to_string())
+}
+
+/// Parse a comma-separated address header into `(display name, address)` pairs.
+fn parse_address_list(raw: Option<&str>) -> Vec<(String, String)> {
+    raw.map(|s| {
+        s.split(',')
+            .map(|x| x.trim())
+            .filter(|x| !x.is_empty())
+            .map(parse_address)
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+/// Decode any RFC 2047 MIME "encoded-words" (`=?charset?enc?text?=`) found in
+/// `input`, leaving ordinary text untouched. `enc` is `B` (base64) or `Q` (the
+/// quoted-printable variant where `_` is a space). Linear whitespace separating
+/// two adjacent encoded-words is dropped; whitespace between an encoded-word and
+/// ordinary text is preserved.
+pub fn decode_encoded_words(input: &str) -> String {
+    enum Seg {
+        Ew(String),
+        Lit(String),
+    }
+    let mut segs: Vec<Seg> = Vec::new();
+    let mut lit = String::new();
+    let mut rest = input;
+    while !rest.is_empty() {
+        if rest.starts_with("=?") {
+            if let Some((decoded, consumed)) = parse_encoded_word(rest) {
+                if !lit.is_empty() {
+                    segs.push(Seg::Lit(std::mem::take(&mut lit)));
+                }
+                segs.push(Seg::Ew(decoded));
+                rest = &rest[consumed..];
+                continue;
+            }
+        }
+        let ch = rest.chars().next().unwrap();
+        lit.push(ch);
+        rest = &rest[ch.len_utf8()..];
+    }
+    if !lit.is_empty() {
+        segs.push(Seg::Lit(lit));
+    }
+
+    let mut out = String::new();
+    for i in 0..segs.len() {
+        match &segs[i] {
+            Seg::Ew(s) => out.push_str(s),
+            Seg::Lit(s) => {
+                let between_ews = i > 0
+                    && i + 1 < segs.len()
+                    && matches!(segs[i - 1], Seg::Ew(_))
+                    && matches!(segs[i + 1], Seg::Ew(_));
+                if !(between_ews && s.trim().is_empty()) {
+                    out.push_str(s);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Parse a single encoded-word at the start of `s` (which must begin with
+/// `=?`), returning the decoded text and the number of bytes consumed.
+fn parse_encoded_word(s: &str) -> Option<(String, usize)> {
+    let body = &s[2..];
+    let q1 = body.find('?')?;
+    let charset = &body[..q1];
+    let after = &body[q1 + 1..];
+    let q2 = after.find('?')?;
+    let enc = &after[..q2];
+    let after2 = &after[q2 + 1..];
+    let end = after2.find("?=")?;
+    let text = &after2[..end];
+    // Per RFC 2047 an encoded-word contains no whitespace; bail if it does so we
+    // don't swallow following tokens.
+    if charset.is_empty() || text.contains(char::is_whitespace) {
+        return None;
+    }
+    let bytes = match enc.to_ascii_uppercase().as_str() {
+        "B" => base64_decode(text)?,
+        "Q" => decode_q(text),
+        _ => return None,
+    };
+    let consumed = 2 + q1 + 1 + q2 + 1 + end + 2;
+    Some((decode_charset(&bytes, charset), consumed))
+}
+
+/// Decode the quoted-printable variant used by RFC 2047 `Q` encoded-words.
+fn decode_q(text: &str) -> Vec<u8> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'_' => {
+                out.push(b' ');
+                i += 1;
+            }
+            b'=' if i + 2 < bytes.len() => {
+                let hi = (bytes[i + 1] as char).to_digit(16);
+                let lo = (bytes[i + 2] as char).to_digit(16);
+                if let (Some(h), Some(l)) = (hi, lo) {
+                    out.push((h * 16 + l) as u8);
+                    i += 3;
+                } else {
+                    out.push(b'=');
+                    i += 1;
+                }
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// A minimal standard-alphabet base64 decoder. Returns `None` on an invalid
+/// character; whitespace is ignored and `=` padding ends the stream.
+fn base64_decode(s: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut buf: u32 = 0;
+    let mut bits = 0u32;
+    for c in s.bytes() {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => break,
+            b'\r' | b'\n' | b' ' | b'\t' => continue,
+            _ => return None,
+        } as u32;
+        buf = (buf << 6) | v;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buf >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+/// Decode `bytes` from the named character set to a `String`. Understands
+/// utf-8, iso-8859-1 and the single-byte windows-125x family; unknown labels
+/// (including the DBCS pages such as 932/936, which are not transcoded) fall
+/// back to Windows-1252, which is a superset of ASCII.
+fn decode_charset(bytes: &[u8], charset: &str) -> String {
+    match charset.trim().to_ascii_lowercase().as_str() {
+        "utf-8" | "utf8" | "us-ascii" | "ascii" | "65001" => {
+            String::from_utf8_lossy(bytes).into_owned()
+        }
+        "iso-8859-1" | "iso8859-1" | "latin1" | "l1" | "28591" => {
+            bytes.iter().map(|&b| b as char).collect()
+        }
+        "windows-1250" | "cp1250" | "1250" => decode_single_byte(bytes, &CP1250),
+        "windows-1251" | "cp1251" | "1251" => decode_single_byte(bytes, &CP1251),
+        "windows-1253" | "cp1253" | "1253" => decode_single_byte(bytes, &CP1253),
+        "windows-1254" | "cp1254" | "1254" => decode_single_byte(bytes, &CP1254),
+        "windows-1255" | "cp1255" | "1255" => decode_single_byte(bytes, &CP1255),
+        "windows-1256" | "cp1256" | "1256" => decode_single_byte(bytes, &CP1256),
+        "windows-1257" | "cp1257" | "1257" => decode_single_byte(bytes, &CP1257),
+        "windows-1258" | "cp1258" | "1258" => decode_single_byte(bytes, &CP1258),
+        _ => decode_windows_1252(bytes),
+    }
+}
+
+/// Decode a single-byte code page whose `0x00..=0x7F` half is ASCII and whose
+/// `0x80..=0xFF` half is given by `high` (indexed by `byte - 0x80`). Unmapped
+/// positions in the table are the Unicode replacement character.
+fn decode_single_byte(bytes: &[u8], high: &[char; 128]) -> String {
+    bytes
+        .iter()
+        .map(|&b| {
+            if b < 0x80 {
+                b as char
+            } else {
+                high[(b - 0x80) as usize]
+            }
+        })
+        .collect()
+}
+
+/// Decode a Windows-1252 byte slice. Bytes outside the `0x80..=0x9F` range map
+/// straight to the matching Latin-1 code point; the punctuation block in that
+/// range uses the CP1252 table, with truly undefined bytes kept as-is.
+fn decode_windows_1252(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&b| match b {
+            0x80 => '\u{20AC}',
+            0x82 => '\u{201A}',
+            0x83 => '\u{0192}',
+            0x84 => '\u{201E}',
+            0x85 => '\u{2026}',
+            0x86 => '\u{2020}',
+            0x87 => '\u{2021}',
+            0x88 => '\u{02C6}',
+            0x89 => '\u{2030}',
+            0x8A => '\u{0160}',
+            0x8B => '\u{2039}',
+            0x8C => '\u{0152}',
+            0x8E => '\u{017D}',
+            0x91 => '\u{2018}',
+            0x92 => '\u{2019}',
+            0x93 => '\u{201C}',
+            0x94 => '\u{201D}',
+            0x95 => '\u{2022}',
+            0x96 => '\u{2013}',
+            0x97 => '\u{2014}',
+            0x98 => '\u{02DC}',
+            0x99 => '\u{2122}',
+            0x9A => '\u{0161}',
+            0x9B => '\u{203A}',
+            0x9C => '\u{0153}',
+            0x9E => '\u{017E}',
+            0x9F => '\u{0178}',
+            other => other as char,
+        })
+        .collect()
+}
+
 fn pack_u8s_to_u16s_le_padded(bytes: &[u8]) -> Vec<u16> {
     let mut result = Vec::with_capacity(bytes.len().div_ceil(2));
     let mut i = 0;
@@ -317,3 +1038,129 @@ fn pack_u8s_to_u16s_le_padded(bytes: &[u8]) -> Vec<u16> {
     }
     result
 }
+
+/// Windows-1250 `0x80..=0xFF` to Unicode (generated from the code page's
+/// canonical mapping; undefined positions are U+FFFD).
+const CP1250: [char; 128] = ['\u{20AC}', '\u{FFFD}', '\u{201A}', '\u{FFFD}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}', '\u{FFFD}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{015A}', '\u{0164}', '\u{017D}', '\u{0179}', '\u{FFFD}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}', '\u{FFFD}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{015B}', '\u{0165}', '\u{017E}', '\u{017A}', '\u{00A0}', '\u{02C7}', '\u{02D8}', '\u{0141}', '\u{00A4}', '\u{0104}', '\u{00A6}', '\u{00A7}', '\u{00A8}', '\u{00A9}', '\u{015E}', '\u{00AB}', '\u{00AC}', '\u{00AD}', '\u{00AE}', '\u{017B}', '\u{00B0}', '\u{00B1}', '\u{02DB}', '\u{0142}', '\u{00B4}', '\u{00B5}', '\u{00B6}', '\u{00B7}', '\u{00B8}', '\u{0105}', '\u{015F}', '\u{00BB}', '\u{013D}', '\u{02DD}', '\u{013E}', '\u{017C}', '\u{0154}', '\u{00C1}', '\u{00C2}', '\u{0102}', '\u{00C4}', '\u{0139}', '\u{0106}', '\u{00C7}', '\u{010C}', '\u{00C9}', '\u{0118}', '\u{00CB}', '\u{011A}', '\u{00CD}', '\u{00CE}', '\u{010E}', '\u{0110}', '\u{0143}', '\u{0147}', '\u{00D3}', '\u{00D4}', '\u{0150}', '\u{00D6}', '\u{00D7}', '\u{0158}', '\u{016E}', '\u{00DA}', '\u{0170}', '\u{00DC}', '\u{00DD}', '\u{0162}', '\u{00DF}', '\u{0155}', '\u{00E1}', '\u{00E2}', '\u{0103}', '\u{00E4}', '\u{013A}', '\u{0107}', '\u{00E7}', '\u{010D}', '\u{00E9}', '\u{0119}', '\u{00EB}', '\u{011B}', '\u{00ED}', '\u{00EE}', '\u{010F}', '\u{0111}', '\u{0144}', '\u{0148}', '\u{00F3}', '\u{00F4}', '\u{0151}', '\u{00F6}', '\u{00F7}', '\u{0159}', '\u{016F}', '\u{00FA}', '\u{0171}', '\u{00FC}', '\u{00FD}', '\u{0163}', '\u{02D9}'];
+
+/// Windows-1251 `0x80..=0xFF` to Unicode (generated from the code page's
+/// canonical mapping; undefined positions are U+FFFD).
+const CP1251: [char; 128] = ['\u{0402}', '\u{0403}', '\u{201A}', '\u{0453}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}', '\u{20AC}', '\u{2030}', '\u{0409}', '\u{2039}', '\u{040A}', '\u{040C}', '\u{040B}', '\u{040F}', '\u{0452}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}', '\u{FFFD}', '\u{2122}', '\u{0459}', '\u{203A}', '\u{045A}', '\u{045C}', '\u{045B}', '\u{045F}', '\u{00A0}', '\u{040E}', '\u{045E}', '\u{0408}', '\u{00A4}', '\u{0490}', '\u{00A6}', '\u{00A7}', '\u{0401}', '\u{00A9}', '\u{0404}', '\u{00AB}', '\u{00AC}', '\u{00AD}', '\u{00AE}', '\u{0407}', '\u{00B0}', '\u{00B1}', '\u{0406}', '\u{0456}', '\u{0491}', '\u{00B5}', '\u{00B6}', '\u{00B7}', '\u{0451}', '\u{2116}', '\u{0454}', '\u{00BB}', '\u{0458}', '\u{0405}', '\u{0455}', '\u{0457}', '\u{0410}', '\u{0411}', '\u{0412}', '\u{0413}', '\u{0414}', '\u{0415}', '\u{0416}', '\u{0417}', '\u{0418}', '\u{0419}', '\u{041A}', '\u{041B}', '\u{041C}', '\u{041D}', '\u{041E}', '\u{041F}', '\u{0420}', '\u{0421}', '\u{0422}', '\u{0423}', '\u{0424}', '\u{0425}', '\u{0426}', '\u{0427}', '\u{0428}', '\u{0429}', '\u{042A}', '\u{042B}', '\u{042C}', '\u{042D}', '\u{042E}', '\u{042F}', '\u{0430}', '\u{0431}', '\u{0432}', '\u{0433}', '\u{0434}', '\u{0435}', '\u{0436}', '\u{0437}', '\u{0438}', '\u{0439}', '\u{043A}', '\u{043B}', '\u{043C}', '\u{043D}', '\u{043E}', '\u{043F}', '\u{0440}', '\u{0441}', '\u{0442}', '\u{0443}', '\u{0444}', '\u{0445}', '\u{0446}', '\u{0447}', '\u{0448}', '\u{0449}', '\u{044A}', '\u{044B}', '\u{044C}', '\u{044D}', '\u{044E}', '\u{044F}'];
+
+/// Windows-1253 `0x80..=0xFF` to Unicode (generated from the code page's
+/// canonical mapping; undefined positions are U+FFFD).
+const CP1253: [char; 128] = ['\u{20AC}', '\u{FFFD}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}', '\u{FFFD}', '\u{2030}', '\u{FFFD}', '\u{2039}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}', '\u{FFFD}', '\u{2122}', '\u{FFFD}', '\u{203A}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{00A0}', '\u{0385}', '\u{0386}', '\u{00A3}', '\u{00A4}', '\u{00A5}', '\u{00A6}', '\u{00A7}', '\u{00A8}', '\u{00A9}', '\u{FFFD}', '\u{00AB}', '\u{00AC}', '\u{00AD}', '\u{00AE}', '\u{2015}', '\u{00B0}', '\u{00B1}', '\u{00B2}', '\u{00B3}', '\u{0384}', '\u{00B5}', '\u{00B6}', '\u{00B7}', '\u{0388}', '\u{0389}', '\u{038A}', '\u{00BB}', '\u{038C}', '\u{00BD}', '\u{038E}', '\u{038F}', '\u{0390}', '\u{0391}', '\u{0392}', '\u{0393}', '\u{0394}', '\u{0395}', '\u{0396}', '\u{0397}', '\u{0398}', '\u{0399}', '\u{039A}', '\u{039B}', '\u{039C}', '\u{039D}', '\u{039E}', '\u{039F}', '\u{03A0}', '\u{03A1}', '\u{FFFD}', '\u{03A3}', '\u{03A4}', '\u{03A5}', '\u{03A6}', '\u{03A7}', '\u{03A8}', '\u{03A9}', '\u{03AA}', '\u{03AB}', '\u{03AC}', '\u{03AD}', '\u{03AE}', '\u{03AF}', '\u{03B0}', '\u{03B1}', '\u{03B2}', '\u{03B3}', '\u{03B4}', '\u{03B5}', '\u{03B6}', '\u{03B7}', '\u{03B8}', '\u{03B9}', '\u{03BA}', '\u{03BB}', '\u{03BC}', '\u{03BD}', '\u{03BE}', '\u{03BF}', '\u{03C0}', '\u{03C1}', '\u{03C2}', '\u{03C3}', '\u{03C4}', '\u{03C5}', '\u{03C6}', '\u{03C7}', '\u{03C8}', '\u{03C9}', '\u{03CA}', '\u{03CB}', '\u{03CC}', '\u{03CD}', '\u{03CE}', '\u{FFFD}'];
+
+/// Windows-1254 `0x80..=0xFF` to Unicode (generated from the code page's
+/// canonical mapping; undefined positions are U+FFFD).
+const CP1254: [char; 128] = ['\u{20AC}', '\u{FFFD}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}', '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}', '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{FFFD}', '\u{FFFD}', '\u{0178}', '\u{00A0}', '\u{00A1}', '\u{00A2}', '\u{00A3}', '\u{00A4}', '\u{00A5}', '\u{00A6}', '\u{00A7}', '\u{00A8}', '\u{00A9}', '\u{00AA}', '\u{00AB}', '\u{00AC}', '\u{00AD}', '\u{00AE}', '\u{00AF}', '\u{00B0}', '\u{00B1}', '\u{00B2}', '\u{00B3}', '\u{00B4}', '\u{00B5}', '\u{00B6}', '\u{00B7}', '\u{00B8}', '\u{00B9}', '\u{00BA}', '\u{00BB}', '\u{00BC}', '\u{00BD}', '\u{00BE}', '\u{00BF}', '\u{00C0}', '\u{00C1}', '\u{00C2}', '\u{00C3}', '\u{00C4}', '\u{00C5}', '\u{00C6}', '\u{00C7}', '\u{00C8}', '\u{00C9}', '\u{00CA}', '\u{00CB}', '\u{00CC}', '\u{00CD}', '\u{00CE}', '\u{00CF}', '\u{011E}', '\u{00D1}', '\u{00D2}', '\u{00D3}', '\u{00D4}', '\u{00D5}', '\u{00D6}', '\u{00D7}', '\u{00D8}', '\u{00D9}', '\u{00DA}', '\u{00DB}', '\u{00DC}', '\u{0130}', '\u{015E}', '\u{00DF}', '\u{00E0}', '\u{00E1}', '\u{00E2}', '\u{00E3}', '\u{00E4}', '\u{00E5}', '\u{00E6}', '\u{00E7}', '\u{00E8}', '\u{00E9}', '\u{00EA}', '\u{00EB}', '\u{00EC}', '\u{00ED}', '\u{00EE}', '\u{00EF}', '\u{011F}', '\u{00F1}', '\u{00F2}', '\u{00F3}', '\u{00F4}', '\u{00F5}', '\u{00F6}', '\u{00F7}', '\u{00F8}', '\u{00F9}', '\u{00FA}', '\u{00FB}', '\u{00FC}', '\u{0131}', '\u{015F}', '\u{00FF}'];
+
+/// Windows-1255 `0x80..=0xFF` to Unicode (generated from the code page's
+/// canonical mapping; undefined positions are U+FFFD).
+const CP1255: [char; 128] = ['\u{20AC}', '\u{FFFD}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}', '\u{02C6}', '\u{2030}', '\u{FFFD}', '\u{2039}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}', '\u{02DC}', '\u{2122}', '\u{FFFD}', '\u{203A}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{00A0}', '\u{00A1}', '\u{00A2}', '\u{00A3}', '\u{20AA}', '\u{00A5}', '\u{00A6}', '\u{00A7}', '\u{00A8}', '\u{00A9}', '\u{00D7}', '\u{00AB}', '\u{00AC}', '\u{00AD}', '\u{00AE}', '\u{00AF}', '\u{00B0}', '\u{00B1}', '\u{00B2}', '\u{00B3}', '\u{00B4}', '\u{00B5}', '\u{00B6}', '\u{00B7}', '\u{00B8}', '\u{00B9}', '\u{00F7}', '\u{00BB}', '\u{00BC}', '\u{00BD}', '\u{00BE}', '\u{00BF}', '\u{05B0}', '\u{05B1}', '\u{05B2}', '\u{05B3}', '\u{05B4}', '\u{05B5}', '\u{05B6}', '\u{05B7}', '\u{05B8}', '\u{05B9}', '\u{FFFD}', '\u{05BB}', '\u{05BC}', '\u{05BD}', '\u{05BE}', '\u{05BF}', '\u{05C0}', '\u{05C1}', '\u{05C2}', '\u{05C3}', '\u{05F0}', '\u{05F1}', '\u{05F2}', '\u{05F3}', '\u{05F4}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{05D0}', '\u{05D1}', '\u{05D2}', '\u{05D3}', '\u{05D4}', '\u{05D5}', '\u{05D6}', '\u{05D7}', '\u{05D8}', '\u{05D9}', '\u{05DA}', '\u{05DB}', '\u{05DC}', '\u{05DD}', '\u{05DE}', '\u{05DF}', '\u{05E0}', '\u{05E1}', '\u{05E2}', '\u{05E3}', '\u{05E4}', '\u{05E5}', '\u{05E6}', '\u{05E7}', '\u{05E8}', '\u{05E9}', '\u{05EA}', '\u{FFFD}', '\u{FFFD}', '\u{200E}', '\u{200F}', '\u{FFFD}'];
+
+/// Windows-1256 `0x80..=0xFF` to Unicode (generated from the code page's
+/// canonical mapping; undefined positions are U+FFFD).
+const CP1256: [char; 128] = ['\u{20AC}', '\u{067E}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}', '\u{02C6}', '\u{2030}', '\u{0679}', '\u{2039}', '\u{0152}', '\u{0686}', '\u{0698}', '\u{0688}', '\u{06AF}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}', '\u{06A9}', '\u{2122}', '\u{0691}', '\u{203A}', '\u{0153}', '\u{200C}', '\u{200D}', '\u{06BA}', '\u{00A0}', '\u{060C}', '\u{00A2}', '\u{00A3}', '\u{00A4}', '\u{00A5}', '\u{00A6}', '\u{00A7}', '\u{00A8}', '\u{00A9}', '\u{06BE}', '\u{00AB}', '\u{00AC}', '\u{00AD}', '\u{00AE}', '\u{00AF}', '\u{00B0}', '\u{00B1}', '\u{00B2}', '\u{00B3}', '\u{00B4}', '\u{00B5}', '\u{00B6}', '\u{00B7}', '\u{00B8}', '\u{00B9}', '\u{061B}', '\u{00BB}', '\u{00BC}', '\u{00BD}', '\u{00BE}', '\u{061F}', '\u{06C1}', '\u{0621}', '\u{0622}', '\u{0623}', '\u{0624}', '\u{0625}', '\u{0626}', '\u{0627}', '\u{0628}', '\u{0629}', '\u{062A}', '\u{062B}', '\u{062C}', '\u{062D}', '\u{062E}', '\u{062F}', '\u{0630}', '\u{0631}', '\u{0632}', '\u{0633}', '\u{0634}', '\u{0635}', '\u{0636}', '\u{00D7}', '\u{0637}', '\u{0638}', '\u{0639}', '\u{063A}', '\u{0640}', '\u{0641}', '\u{0642}', '\u{0643}', '\u{00E0}', '\u{0644}', '\u{00E2}', '\u{0645}', '\u{0646}', '\u{0647}', '\u{0648}', '\u{00E7}', '\u{00E8}', '\u{00E9}', '\u{00EA}', '\u{00EB}', '\u{0649}', '\u{064A}', '\u{00EE}', '\u{00EF}', '\u{064B}', '\u{064C}', '\u{064D}', '\u{064E}', '\u{00F4}', '\u{064F}', '\u{0650}', '\u{00F7}', '\u{0651}', '\u{00F9}', '\u{0652}', '\u{00FB}', '\u{00FC}', '\u{200E}', '\u{200F}', '\u{06D2}'];
+
+/// Windows-1257 `0x80..=0xFF` to Unicode (generated from the code page's
+/// canonical mapping; undefined positions are U+FFFD).
+const CP1257: [char; 128] = ['\u{20AC}', '\u{FFFD}', '\u{201A}', '\u{FFFD}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}', '\u{FFFD}', '\u{2030}', '\u{FFFD}', '\u{2039}', '\u{FFFD}', '\u{00A8}', '\u{02C7}', '\u{00B8}', '\u{FFFD}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}', '\u{FFFD}', '\u{2122}', '\u{FFFD}', '\u{203A}', '\u{FFFD}', '\u{00AF}', '\u{02DB}', '\u{FFFD}', '\u{00A0}', '\u{FFFD}', '\u{00A2}', '\u{00A3}', '\u{00A4}', '\u{FFFD}', '\u{00A6}', '\u{00A7}', '\u{00D8}', '\u{00A9}', '\u{0156}', '\u{00AB}', '\u{00AC}', '\u{00AD}', '\u{00AE}', '\u{00C6}', '\u{00B0}', '\u{00B1}', '\u{00B2}', '\u{00B3}', '\u{00B4}', '\u{00B5}', '\u{00B6}', '\u{00B7}', '\u{00F8}', '\u{00B9}', '\u{0157}', '\u{00BB}', '\u{00BC}', '\u{00BD}', '\u{00BE}', '\u{00E6}', '\u{0104}', '\u{012E}', '\u{0100}', '\u{0106}', '\u{00C4}', '\u{00C5}', '\u{0118}', '\u{0112}', '\u{010C}', '\u{00C9}', '\u{0179}', '\u{0116}', '\u{0122}', '\u{0136}', '\u{012A}', '\u{013B}', '\u{0160}', '\u{0143}', '\u{0145}', '\u{00D3}', '\u{014C}', '\u{00D5}', '\u{00D6}', '\u{00D7}', '\u{0172}', '\u{0141}', '\u{015A}', '\u{016A}', '\u{00DC}', '\u{017B}', '\u{017D}', '\u{00DF}', '\u{0105}', '\u{012F}', '\u{0101}', '\u{0107}', '\u{00E4}', '\u{00E5}', '\u{0119}', '\u{0113}', '\u{010D}', '\u{00E9}', '\u{017A}', '\u{0117}', '\u{0123}', '\u{0137}', '\u{012B}', '\u{013C}', '\u{0161}', '\u{0144}', '\u{0146}', '\u{00F3}', '\u{014D}', '\u{00F5}', '\u{00F6}', '\u{00F7}', '\u{0173}', '\u{0142}', '\u{015B}', '\u{016B}', '\u{00FC}', '\u{017C}', '\u{017E}', '\u{02D9}'];
+
+/// Windows-1258 `0x80..=0xFF` to Unicode (generated from the code page's
+/// canonical mapping; undefined positions are U+FFFD).
+const CP1258: [char; 128] = ['\u{20AC}', '\u{FFFD}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}', '\u{02C6}', '\u{2030}', '\u{FFFD}', '\u{2039}', '\u{0152}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{FFFD}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}', '\u{02DC}', '\u{2122}', '\u{FFFD}', '\u{203A}', '\u{0153}', '\u{FFFD}', '\u{FFFD}', '\u{0178}', '\u{00A0}', '\u{00A1}', '\u{00A2}', '\u{00A3}', '\u{00A4}', '\u{00A5}', '\u{00A6}', '\u{00A7}', '\u{00A8}', '\u{00A9}', '\u{00AA}', '\u{00AB}', '\u{00AC}', '\u{00AD}', '\u{00AE}', '\u{00AF}', '\u{00B0}', '\u{00B1}', '\u{00B2}', '\u{00B3}', '\u{00B4}', '\u{00B5}', '\u{00B6}', '\u{00B7}', '\u{00B8}', '\u{00B9}', '\u{00BA}', '\u{00BB}', '\u{00BC}', '\u{00BD}', '\u{00BE}', '\u{00BF}', '\u{00C0}', '\u{00C1}', '\u{00C2}', '\u{0102}', '\u{00C4}', '\u{00C5}', '\u{00C6}', '\u{00C7}', '\u{00C8}', '\u{00C9}', '\u{00CA}', '\u{00CB}', '\u{0300}', '\u{00CD}', '\u{00CE}', '\u{00CF}', '\u{0110}', '\u{00D1}', '\u{0309}', '\u{00D3}', '\u{00D4}', '\u{01A0}', '\u{00D6}', '\u{00D7}', '\u{00D8}', '\u{00D9}', '\u{00DA}', '\u{00DB}', '\u{00DC}', '\u{01AF}', '\u{0303}', '\u{00DF}', '\u{00E0}', '\u{00E1}', '\u{00E2}', '\u{0103}', '\u{00E4}', '\u{00E5}', '\u{00E6}', '\u{00E7}', '\u{00E8}', '\u{00E9}', '\u{00EA}', '\u{00EB}', '\u{0301}', '\u{00ED}', '\u{00EE}', '\u{00EF}', '\u{0111}', '\u{00F1}', '\u{0323}', '\u{00F3}', '\u{00F4}', '\u{01A1}', '\u{00F6}', '\u{00F7}', '\u{00F8}', '\u{00F9}', '\u{00FA}', '\u{00FB}', '\u{00FC}', '\u{01B0}', '\u{20AB}', '\u{00FF}'];
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn folded_header_continuation_is_joined() {
+        let raw = "Subject: line one\r\n\tand line two\r\nTo: a@b\r\n\r\nbody";
+        let headers = parse_raw_headers(raw);
+        assert_eq!(
+            header_get(&headers, "subject"),
+            Some("line one and line two")
+        );
+        assert_eq!(header_get(&headers, "To"), Some("a@b"));
+    }
+
+    #[test]
+    fn adjacent_encoded_words_drop_separating_whitespace() {
+        // Whitespace between two encoded-words is dropped, ...
+        assert_eq!(
+            decode_encoded_words("=?utf-8?Q?a?= =?utf-8?Q?b?="),
+            "ab"
+        );
+        // ... but whitespace between an encoded-word and literal text is kept.
+        assert_eq!(decode_encoded_words("=?utf-8?Q?a?= b"), "a b");
+    }
+
+    #[test]
+    fn decodes_base64_and_quoted_printable_words() {
+        assert_eq!(decode_encoded_words("=?utf-8?B?aGVsbG8=?="), "hello");
+        // `_` is a space and `=XX` a hex byte in the Q variant.
+        assert_eq!(decode_encoded_words("=?utf-8?Q?a_b=3Dc?="), "a b=c");
+        assert_eq!(base64_decode("aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(decode_q("a_b=3Dc"), b"a b=c");
+    }
+
+    #[test]
+    fn decodes_a_non_1252_codepage_byte() {
+        // 0xC0 is CYRILLIC CAPITAL LETTER A (U+0410) in windows-1251, not the
+        // À it would be under the 1252 fallback.
+        assert_eq!(decode_charset(&[0xC0], "windows-1251"), "\u{0410}");
+    }
+
+    fn sample_email(subject: &str, body: &str, from_name: &str) -> Email {
+        Email {
+            from: Some((from_name.to_string(), "sender@example.com".to_string())),
+            sent_date: None,
+            to: vec![],
+            cc: vec![],
+            bcc: vec![],
+            subject: Some(subject.to_string()),
+            body: Some(body.to_string()),
+            attachments: vec![],
+            embedded_messages: vec![],
+            raw_headers: vec![],
+        }
+    }
+
+    #[test]
+    fn base64_wrap_limits_lines_to_76_columns() {
+        let wrapped = base64_wrap(&[b'a'; 200]);
+        assert!(wrapped.contains("\r\n"));
+        assert!(wrapped.split("\r\n").all(|line| line.len() <= 76));
+    }
+
+    #[test]
+    fn rtf_body_is_not_mislabeled_as_html() {
+        let eml = String::from_utf8(
+            sample_email("s", "{\\rtf1\\ansi decompressed}", "Name")
+                .to_eml()
+                .unwrap(),
+        )
+        .unwrap();
+        assert!(eml.contains("Content-Type: application/rtf"));
+        assert!(!eml.contains("text/html"));
+    }
+
+    #[test]
+    fn crlf_in_headers_cannot_inject_extra_lines() {
+        let eml = String::from_utf8(
+            sample_email(
+                "hi\r\nX-Injected: subject",
+                "body",
+                "Evil\r\nBcc: victim@example.com",
+            )
+            .to_eml()
+            .unwrap(),
+        )
+        .unwrap();
+        assert!(!eml.lines().any(|l| l.starts_with("X-Injected:")));
+        assert!(!eml.lines().any(|l| l.starts_with("Bcc:")));
+    }
+}